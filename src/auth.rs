@@ -0,0 +1,142 @@
+// src/auth.rs
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use chrono::Local;
+use rusqlite::{params, Connection};
+
+/// Errors that can occur while registering or authenticating a user.
+#[derive(Debug)]
+pub enum AuthError {
+    UsernameTaken,
+    InvalidCredentials,
+    Database(String),
+    Hashing(String),
+}
+
+impl std::fmt::Display for AuthError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AuthError::UsernameTaken => write!(f, "username already registered"),
+            AuthError::InvalidCredentials => write!(f, "invalid username or password"),
+            AuthError::Database(e) => write!(f, "database error: {}", e),
+            AuthError::Hashing(e) => write!(f, "hashing error: {}", e),
+        }
+    }
+}
+
+pub fn create_users_table(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS users (
+            username TEXT PRIMARY KEY,
+            password_hash TEXT NOT NULL,
+            created_at TEXT NOT NULL
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+fn hash_password(password: &str) -> Result<String, AuthError> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| AuthError::Hashing(e.to_string()))
+}
+
+fn verify_password(password: &str, phc_string: &str) -> bool {
+    match PasswordHash::new(phc_string) {
+        Ok(parsed) => Argon2::default()
+            .verify_password(password.as_bytes(), &parsed)
+            .is_ok(),
+        Err(_) => false,
+    }
+}
+
+/// Registers a new user, hashing `password` with Argon2id before storing it.
+pub fn register_user(conn: &Connection, username: &str, password: &str) -> Result<(), AuthError> {
+    let exists: bool = conn
+        .query_row(
+            "SELECT 1 FROM users WHERE username = ?1",
+            params![username],
+            |_| Ok(true),
+        )
+        .unwrap_or(false);
+    if exists {
+        return Err(AuthError::UsernameTaken);
+    }
+
+    let password_hash = hash_password(password)?;
+    let created_at = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    conn.execute(
+        "INSERT INTO users (username, password_hash, created_at) VALUES (?1, ?2, ?3)",
+        params![username, password_hash, created_at],
+    )
+    .map_err(|e| AuthError::Database(e.to_string()))?;
+    Ok(())
+}
+
+/// Verifies `password` against the stored hash for `username` in constant time.
+pub fn authenticate_user(conn: &Connection, username: &str, password: &str) -> Result<(), AuthError> {
+    let password_hash: Option<String> = conn
+        .query_row(
+            "SELECT password_hash FROM users WHERE username = ?1",
+            params![username],
+            |row| row.get(0),
+        )
+        .ok();
+
+    match password_hash {
+        Some(hash) if verify_password(password, &hash) => Ok(()),
+        _ => Err(AuthError::InvalidCredentials),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        create_users_table(&conn).unwrap();
+        conn
+    }
+
+    #[test]
+    fn register_then_authenticate_round_trips() {
+        let conn = setup();
+        register_user(&conn, "alice", "hunter2").unwrap();
+        assert!(authenticate_user(&conn, "alice", "hunter2").is_ok());
+    }
+
+    #[test]
+    fn authenticate_rejects_wrong_password() {
+        let conn = setup();
+        register_user(&conn, "alice", "hunter2").unwrap();
+        assert!(matches!(authenticate_user(&conn, "alice", "wrong"), Err(AuthError::InvalidCredentials)));
+    }
+
+    #[test]
+    fn authenticate_rejects_unknown_user() {
+        let conn = setup();
+        assert!(matches!(authenticate_user(&conn, "nobody", "hunter2"), Err(AuthError::InvalidCredentials)));
+    }
+
+    #[test]
+    fn register_rejects_duplicate_username() {
+        let conn = setup();
+        register_user(&conn, "alice", "hunter2").unwrap();
+        assert!(matches!(register_user(&conn, "alice", "different"), Err(AuthError::UsernameTaken)));
+    }
+
+    #[test]
+    fn passwords_are_hashed_not_stored_in_plaintext() {
+        let conn = setup();
+        register_user(&conn, "alice", "hunter2").unwrap();
+        let stored: String = conn
+            .query_row("SELECT password_hash FROM users WHERE username = 'alice'", [], |row| row.get(0))
+            .unwrap();
+        assert_ne!(stored, "hunter2");
+    }
+}
@@ -0,0 +1,251 @@
+// src/bot.rs
+
+/// A recognized `!`-prefixed inline command, parsed from a public chat line
+/// before it is dispatched.
+pub enum BotCommand {
+    Owoify(String),
+    Mock(String),
+    Leet(String),
+    Calc(String),
+    Waifu(String),
+}
+
+pub fn parse(content: &str) -> Option<BotCommand> {
+    let content = content.trim();
+    if let Some(rest) = content.strip_prefix("!owo ") {
+        return Some(BotCommand::Owoify(rest.to_string()));
+    }
+    if let Some(rest) = content.strip_prefix("!mock ") {
+        return Some(BotCommand::Mock(rest.to_string()));
+    }
+    if let Some(rest) = content.strip_prefix("!leet ") {
+        return Some(BotCommand::Leet(rest.to_string()));
+    }
+    if let Some(rest) = content.strip_prefix("!calc ") {
+        return Some(BotCommand::Calc(rest.to_string()));
+    }
+    if let Some(rest) = content.strip_prefix("!waifu") {
+        return Some(BotCommand::Waifu(rest.trim().to_string()));
+    }
+    None
+}
+
+pub fn owoify(input: &str) -> String {
+    input
+        .replace("ove", "uv")
+        .replace('r', "w")
+        .replace('l', "w")
+        .replace('R', "W")
+        .replace('L', "W")
+}
+
+pub fn mock(input: &str) -> String {
+    input
+        .chars()
+        .enumerate()
+        .map(|(i, c)| if i % 2 == 0 { c.to_ascii_uppercase() } else { c.to_ascii_lowercase() })
+        .collect()
+}
+
+pub fn leet(input: &str) -> String {
+    input
+        .chars()
+        .map(|c| match c.to_ascii_lowercase() {
+            'a' => '4',
+            'e' => '3',
+            'i' => '1',
+            'o' => '0',
+            's' => '5',
+            't' => '7',
+            _ => c,
+        })
+        .collect()
+}
+
+/// A minimal recursive-descent evaluator for `+ - * /`, parentheses, and unary
+/// minus over floating-point literals — just enough for `!calc`.
+pub fn calc(expr: &str) -> Result<f64, String> {
+    let tokens = tokenize(expr)?;
+    let mut parser = CalcParser { tokens, pos: 0 };
+    let value = parser.parse_expr()?;
+    if parser.pos != parser.tokens.len() {
+        return Err("unexpected trailing input".to_string());
+    }
+    Ok(value)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    LParen,
+    RParen,
+}
+
+fn tokenize(expr: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let chars: Vec<char> = expr.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            ' ' | '\t' => {}
+            '+' => tokens.push(Token::Plus),
+            '-' => tokens.push(Token::Minus),
+            '*' => tokens.push(Token::Star),
+            '/' => tokens.push(Token::Slash),
+            '(' => tokens.push(Token::LParen),
+            ')' => tokens.push(Token::RParen),
+            _ if c.is_ascii_digit() || c == '.' => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                    i += 1;
+                }
+                let text: String = chars[start..i].iter().collect();
+                let number = text.parse::<f64>().map_err(|_| format!("invalid number '{}'", text))?;
+                tokens.push(Token::Number(number));
+                continue;
+            }
+            _ => return Err(format!("unexpected character '{}'", c)),
+        }
+        i += 1;
+    }
+    Ok(tokens)
+}
+
+struct CalcParser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl CalcParser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn parse_expr(&mut self) -> Result<f64, String> {
+        let mut value = self.parse_term()?;
+        loop {
+            match self.peek() {
+                Some(Token::Plus) => {
+                    self.pos += 1;
+                    value += self.parse_term()?;
+                }
+                Some(Token::Minus) => {
+                    self.pos += 1;
+                    value -= self.parse_term()?;
+                }
+                _ => break,
+            }
+        }
+        Ok(value)
+    }
+
+    fn parse_term(&mut self) -> Result<f64, String> {
+        let mut value = self.parse_unary()?;
+        loop {
+            match self.peek() {
+                Some(Token::Star) => {
+                    self.pos += 1;
+                    value *= self.parse_unary()?;
+                }
+                Some(Token::Slash) => {
+                    self.pos += 1;
+                    let divisor = self.parse_unary()?;
+                    if divisor == 0.0 {
+                        return Err("division by zero".to_string());
+                    }
+                    value /= divisor;
+                }
+                _ => break,
+            }
+        }
+        Ok(value)
+    }
+
+    fn parse_unary(&mut self) -> Result<f64, String> {
+        if let Some(Token::Minus) = self.peek() {
+            self.pos += 1;
+            return Ok(-self.parse_unary()?);
+        }
+        self.parse_primary()
+    }
+
+    fn parse_primary(&mut self) -> Result<f64, String> {
+        match self.tokens.get(self.pos).cloned() {
+            Some(Token::Number(n)) => {
+                self.pos += 1;
+                Ok(n)
+            }
+            Some(Token::LParen) => {
+                self.pos += 1;
+                let value = self.parse_expr()?;
+                match self.tokens.get(self.pos) {
+                    Some(Token::RParen) => {
+                        self.pos += 1;
+                        Ok(value)
+                    }
+                    _ => Err("expected ')'".to_string()),
+                }
+            }
+            _ => Err("expected a number or '('".to_string()),
+        }
+    }
+}
+
+/// Fetches an image URL for `!waifu <category>` from a configurable HTTP API.
+pub struct WaifuClient {
+    http: reqwest::Client,
+    base_url: String,
+}
+
+impl WaifuClient {
+    pub fn new(base_url: String) -> Self {
+        WaifuClient { http: reqwest::Client::new(), base_url }
+    }
+
+    pub async fn fetch_image_url(&self, category: &str) -> Result<String, String> {
+        #[derive(serde::Deserialize)]
+        struct WaifuResponse {
+            url: String,
+        }
+
+        let category = if category.is_empty() { "waifu" } else { category };
+        self.http
+            .get(format!("{}/{}", self.base_url, category))
+            .send()
+            .await
+            .map_err(|e| e.to_string())?
+            .json::<WaifuResponse>()
+            .await
+            .map(|resp| resp.url)
+            .map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn calc_respects_operator_precedence_and_parens() {
+        assert_eq!(calc("2 + 3 * 4"), Ok(14.0));
+        assert_eq!(calc("(2 + 3) * 4"), Ok(20.0));
+        assert_eq!(calc("-2 * -3"), Ok(6.0));
+    }
+
+    #[test]
+    fn calc_rejects_division_by_zero() {
+        assert!(calc("1 / 0").is_err());
+    }
+
+    #[test]
+    fn calc_rejects_malformed_input() {
+        assert!(calc("2 +").is_err());
+        assert!(calc("(1 + 2").is_err());
+        assert!(calc("2 3").is_err());
+    }
+}
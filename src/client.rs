@@ -0,0 +1,362 @@
+// src/client.rs
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use crossterm::execute;
+use crossterm::terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen};
+use futures_util::{SinkExt, StreamExt};
+use ratatui::backend::CrosstermBackend;
+use ratatui::layout::{Constraint, Direction, Layout};
+use ratatui::style::{Color, Style};
+use ratatui::text::Text;
+use ratatui::widgets::{Block, Borders, Paragraph};
+use ratatui::Terminal;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio_tungstenite::connect_async;
+use tokio_tungstenite::tungstenite::protocol::Message;
+
+use crate::protocol::{RequestContainer, RequestKind, ResponseContainer, ResponseKind};
+
+/// Scrollable chat history. `count` is the number of terminal rows the
+/// buffered lines occupy once wrapped to `width`, recomputed whenever a line
+/// is pushed or the terminal is resized; `offset` is clamped to
+/// `count - height` so scrolling can never run past the top (`0`) or bottom (`count - height`) of
+/// the buffer. While `follow` is set, a growing `count` pulls `offset` along
+/// with it so new lines auto-scroll into view; scrolling up at all disables
+/// `follow` until the user scrolls back down to the bottom.
+pub struct History {
+    lines: Vec<String>,
+    pub offset: usize,
+    pub count: usize,
+    pub height: usize,
+    pub width: usize,
+    follow: bool,
+}
+
+impl History {
+    pub fn new(width: usize, height: usize) -> Self {
+        History { lines: Vec::new(), offset: 0, count: 0, height, width: width.max(1), follow: true }
+    }
+
+    pub fn push(&mut self, line: String) {
+        self.lines.push(line);
+        self.recompute();
+    }
+
+    pub fn resize(&mut self, width: usize, height: usize) {
+        self.width = width.max(1);
+        self.height = height;
+        self.recompute();
+    }
+
+    fn recompute(&mut self) {
+        self.count = self
+            .lines
+            .iter()
+            .map(|line| (line.chars().count() / self.width) + 1)
+            .sum();
+        if self.follow {
+            self.offset = self.count.saturating_sub(self.height);
+        } else {
+            self.clamp_offset();
+        }
+    }
+
+    fn clamp_offset(&mut self) {
+        let max_offset = self.count.saturating_sub(self.height);
+        if self.offset > max_offset {
+            self.offset = max_offset;
+        }
+    }
+
+    /// Scrolls toward older lines, saturating at `0`, and stops auto-scrolling.
+    pub fn scroll_up(&mut self, lines: usize) {
+        self.offset = self.offset.saturating_sub(lines);
+        self.follow = false;
+    }
+
+    /// Scrolls toward newer lines, never exceeding `count - height`. Resumes
+    /// auto-scrolling once it reaches the bottom.
+    pub fn scroll_down(&mut self, lines: usize) {
+        let max_offset = self.count.saturating_sub(self.height);
+        self.offset = (self.offset + lines).min(max_offset);
+        self.follow = self.offset == max_offset;
+    }
+
+    pub fn rendered(&self) -> Text<'_> {
+        Text::from(self.lines.join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn auto_scrolls_to_bottom_as_lines_arrive() {
+        let mut history = History::new(80, 5);
+        for i in 0..20 {
+            history.push(format!("line {}", i));
+        }
+        assert_eq!(history.offset, history.count.saturating_sub(history.height));
+    }
+
+    #[test]
+    fn scroll_up_moves_toward_zero_and_stops_following() {
+        let mut history = History::new(80, 5);
+        for i in 0..20 {
+            history.push(format!("line {}", i));
+        }
+        let bottom = history.offset;
+        history.scroll_up(3);
+        assert_eq!(history.offset, bottom - 3);
+
+        history.push("line 20".to_string());
+        assert_eq!(history.offset, bottom - 3, "should not auto-scroll while scrolled up");
+    }
+
+    #[test]
+    fn scroll_down_resumes_following_at_the_bottom() {
+        let mut history = History::new(80, 5);
+        for i in 0..20 {
+            history.push(format!("line {}", i));
+        }
+        history.scroll_up(5);
+        let max_offset = history.count.saturating_sub(history.height);
+        history.scroll_down(5);
+        assert_eq!(history.offset, max_offset);
+
+        history.push("line 20".to_string());
+        assert_eq!(history.offset, history.count.saturating_sub(history.height), "should resume following");
+    }
+
+    #[test]
+    fn scroll_up_saturates_at_zero() {
+        let mut history = History::new(80, 5);
+        history.push("only line".to_string());
+        history.scroll_up(100);
+        assert_eq!(history.offset, 0);
+    }
+}
+
+fn parse_command(line: String) -> RequestKind {
+    if let Some(rest) = line.strip_prefix("/msg ") {
+        let parts: Vec<&str> = rest.splitn(2, ' ').collect();
+        if parts.len() == 2 {
+            return RequestKind::SendPrivate { to: parts[0].trim().to_string(), content: parts[1].trim().to_string() };
+        }
+    }
+    if let Some(room) = line.strip_prefix("/join ") {
+        return RequestKind::JoinRoom { room: room.trim().to_string() };
+    }
+    if let Some(room) = line.strip_prefix("/leave ") {
+        return RequestKind::LeaveRoom { room: room.trim().to_string() };
+    }
+    if let Some(rest) = line.strip_prefix("/topic ") {
+        let parts: Vec<&str> = rest.splitn(2, ' ').collect();
+        if parts.len() == 2 {
+            return RequestKind::SetTopic { room: parts[0].trim().to_string(), topic: parts[1].trim().to_string() };
+        }
+    }
+    if line.trim() == "/rooms" {
+        return RequestKind::ListRooms;
+    }
+    if let Some(keyword) = line.strip_prefix("/search ") {
+        return RequestKind::Search { keyword: keyword.trim().to_string() };
+    }
+    RequestKind::SendPublic { content: line }
+}
+
+/// Builds a history page request, using `before` (the `id` of the oldest row
+/// loaded so far) to page further back on each call.
+fn history_request(before: Option<i64>) -> RequestKind {
+    RequestKind::QueryHistory { limit: 10, before, after: None, room: None, peer: None }
+}
+
+pub async fn run() -> io::Result<()> {
+    print!("Enter your username: ");
+    io::stdout().flush().unwrap();
+    let mut username = String::new();
+    io::stdin().read_line(&mut username).unwrap();
+    let username = username.trim().to_string();
+
+    print!("Register as a new user? [y/N]: ");
+    io::stdout().flush().unwrap();
+    let mut choice = String::new();
+    io::stdin().read_line(&mut choice).unwrap();
+    let is_registering = choice.trim().eq_ignore_ascii_case("y");
+
+    print!("Enter your password: ");
+    io::stdout().flush().unwrap();
+    let mut password = String::new();
+    io::stdin().read_line(&mut password).unwrap();
+    let password = password.trim().to_string();
+
+    let (ws_stream, _) = connect_async("ws://127.0.0.1:8080").await.expect("Failed to connect");
+    let (mut write, mut read) = ws_stream.split();
+
+    let next_id = AtomicU64::new(1);
+    let auth_kind = if is_registering {
+        RequestKind::Register { username: username.clone(), password }
+    } else {
+        RequestKind::Auth { username: username.clone(), password }
+    };
+    let auth_request = RequestContainer { request_id: next_id.fetch_add(1, Ordering::Relaxed), kind: auth_kind };
+    write.send(Message::Text(serde_json::to_string(&auth_request).unwrap())).await.unwrap();
+
+    match read.next().await {
+        Some(Ok(Message::Text(resp))) => {
+            let response: ResponseContainer = serde_json::from_str(&resp).expect("Failed to parse auth response");
+            if let ResponseKind::Error { message } = response.kind {
+                println!("{}", message);
+                return Ok(());
+            }
+        }
+        _ => {
+            eprintln!("Failed to authenticate.");
+            return Ok(());
+        }
+    }
+
+    enable_raw_mode()?;
+    let mut stdout = io::stdout();
+    execute!(stdout, EnterAlternateScreen)?;
+    let backend = CrosstermBackend::new(stdout);
+    let mut terminal = Terminal::new(backend)?;
+
+    let size = terminal.size()?;
+    let history = Arc::new(Mutex::new(History::new(size.width.saturating_sub(2) as usize, size.height.saturating_sub(6) as usize)));
+
+    let history_reader = Arc::clone(&history);
+    let history_oldest = Arc::new(Mutex::new(None::<i64>));
+    let history_oldest_reader = Arc::clone(&history_oldest);
+    let username_for_reader = username.clone();
+    let reader_task = tokio::spawn(async move {
+        while let Some(Ok(msg)) = read.next().await {
+            let Message::Text(text) = msg else { continue };
+            let Ok(response) = serde_json::from_str::<ResponseContainer>(&text) else { continue };
+            let line = match response.kind {
+                ResponseKind::HistoryRow { id, line } => {
+                    *history_oldest_reader.lock().unwrap() = Some(id);
+                    line
+                }
+                ResponseKind::Message { line } => line,
+                ResponseKind::Error { message } => format!("[Error] {}", message),
+                ResponseKind::RoomList { rooms } => format!("Rooms: {}", rooms.join(", ")),
+                ResponseKind::HistoryBegin => "--- History ---".to_string(),
+                ResponseKind::HistoryEnd => "--- End of History ---".to_string(),
+                ResponseKind::Ok => continue,
+            };
+            let _ = &username_for_reader;
+            history_reader.lock().unwrap().push(line);
+        }
+    });
+
+    let mut input = String::new();
+    let result = run_event_loop(&mut terminal, &history, &history_oldest, &mut input, &mut write, &next_id).await;
+
+    reader_task.abort();
+    disable_raw_mode()?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    terminal.show_cursor()?;
+
+    result
+}
+
+/// Requests the next page of older history when the user scrolls past the
+/// top of what's currently loaded, enabling infinite scrollback. Returns
+/// `true` if a request was sent (the caller should skip its own scroll in
+/// that case, since the new page will arrive and adjust the view itself).
+async fn load_more_history(
+    history: &Arc<Mutex<History>>,
+    history_oldest: &Arc<Mutex<Option<i64>>>,
+    write: &mut (impl futures_util::Sink<Message, Error = tokio_tungstenite::tungstenite::Error> + Unpin),
+    next_id: &AtomicU64,
+) -> bool {
+    if history.lock().unwrap().offset != 0 {
+        return false;
+    }
+    let before = *history_oldest.lock().unwrap();
+    if before.is_none() {
+        return false;
+    }
+    let request = RequestContainer { request_id: next_id.fetch_add(1, Ordering::Relaxed), kind: history_request(before) };
+    let _ = write.send(Message::Text(serde_json::to_string(&request).unwrap())).await;
+    true
+}
+
+async fn run_event_loop(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    history: &Arc<Mutex<History>>,
+    history_oldest: &Arc<Mutex<Option<i64>>>,
+    input: &mut String,
+    write: &mut (impl futures_util::Sink<Message, Error = tokio_tungstenite::tungstenite::Error> + Unpin),
+    next_id: &AtomicU64,
+) -> io::Result<()> {
+    loop {
+        let size = terminal.size()?;
+        {
+            let mut history = history.lock().unwrap();
+            history.resize(size.width.saturating_sub(2) as usize, size.height.saturating_sub(6) as usize);
+        }
+
+        terminal.draw(|frame| {
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Min(3), Constraint::Length(3)])
+                .split(frame.size());
+
+            let history = history.lock().unwrap();
+            let history_widget = Paragraph::new(history.rendered())
+                .block(Block::default().borders(Borders::ALL).title("Messages"))
+                .scroll((history.offset as u16, 0));
+            frame.render_widget(history_widget, chunks[0]);
+
+            let input_widget = Paragraph::new(input.as_str())
+                .style(Style::default().fg(Color::Yellow))
+                .block(Block::default().borders(Borders::ALL).title("Message (Enter to send, Esc to quit)"));
+            frame.render_widget(input_widget, chunks[1]);
+        })?;
+
+        if event::poll(Duration::from_millis(100))? {
+            if let Event::Key(key) = event::read()? {
+                if key.kind != KeyEventKind::Press {
+                    continue;
+                }
+                match key.code {
+                    KeyCode::Esc => return Ok(()),
+                    KeyCode::Enter => {
+                        if !input.is_empty() {
+                            let line = std::mem::take(input);
+                            let kind = if line.trim() == "/history" {
+                                history_request(*history_oldest.lock().unwrap())
+                            } else {
+                                parse_command(line)
+                            };
+                            let request = RequestContainer { request_id: next_id.fetch_add(1, Ordering::Relaxed), kind };
+                            let _ = write.send(Message::Text(serde_json::to_string(&request).unwrap())).await;
+                        }
+                    }
+                    KeyCode::Backspace => {
+                        input.pop();
+                    }
+                    KeyCode::Up => {
+                        if !load_more_history(history, history_oldest, write, next_id).await {
+                            history.lock().unwrap().scroll_up(1);
+                        }
+                    }
+                    KeyCode::Down => history.lock().unwrap().scroll_down(1),
+                    KeyCode::PageUp => {
+                        if !load_more_history(history, history_oldest, write, next_id).await {
+                            history.lock().unwrap().scroll_up(10);
+                        }
+                    }
+                    KeyCode::PageDown => history.lock().unwrap().scroll_down(10),
+                    KeyCode::Char(c) => input.push(c),
+                    _ => {}
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,200 @@
+// src/cluster.rs
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+/// Deterministically maps each room name to the node responsible for hosting
+/// it, so every node in the cluster routes a given room to the same place
+/// without needing to coordinate.
+pub struct ClusterMetadata {
+    nodes: Vec<String>,
+}
+
+impl ClusterMetadata {
+    /// `nodes` should list every node in the cluster, including this one.
+    /// The list is sorted and deduplicated, so its input order does not matter.
+    pub fn new(mut nodes: Vec<String>) -> Self {
+        nodes.sort();
+        nodes.dedup();
+        ClusterMetadata { nodes }
+    }
+
+    pub fn owner_of(&self, room: &str) -> &str {
+        if self.nodes.len() <= 1 {
+            return self.nodes.first().map(String::as_str).unwrap_or_default();
+        }
+        let mut hasher = DefaultHasher::new();
+        room.hash(&mut hasher);
+        let index = (hasher.finish() as usize) % self.nodes.len();
+        &self.nodes[index]
+    }
+
+    pub fn is_local(&self, room: &str, self_node: &str) -> bool {
+        self.nodes.len() <= 1 || self.owner_of(room) == self_node
+    }
+}
+
+/// Tracks which remote nodes currently have at least one subscriber for a
+/// room owned by this node, so locally-owned public messages are forwarded
+/// only to nodes that actually need them.
+#[derive(Default)]
+pub struct Broadcasting {
+    subscribers: Mutex<HashMap<String, HashSet<String>>>,
+}
+
+impl Broadcasting {
+    pub fn new() -> Self {
+        Broadcasting::default()
+    }
+
+    pub fn subscribe(&self, room: &str, node: &str) {
+        self.subscribers
+            .lock()
+            .unwrap()
+            .entry(room.to_string())
+            .or_default()
+            .insert(node.to_string());
+    }
+
+    pub fn subscribers_for(&self, room: &str) -> HashSet<String> {
+        self.subscribers.lock().unwrap().get(room).cloned().unwrap_or_default()
+    }
+
+    /// Drops `node` from `room`'s subscriber set, e.g. once it has no more
+    /// local members for that room and stops needing forwarded publishes.
+    pub fn unsubscribe(&self, room: &str, node: &str) {
+        if let Some(nodes) = self.subscribers.lock().unwrap().get_mut(room) {
+            nodes.remove(node);
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ClusterPublish {
+    pub room: String,
+    pub from: String,
+    pub content: String,
+    pub timestamp: String,
+    /// Marks a bot-authored reply, so the receiving node reconstructs it as
+    /// a `ChatMessage::System` instead of a `ChatMessage::Public` from `from`.
+    pub is_system: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ClusterSubscribe {
+    pub room: String,
+    pub node: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ClusterUnsubscribe {
+    pub room: String,
+    pub node: String,
+}
+
+/// Lightweight client used for node-to-node calls: forwarding a public
+/// message to the node that owns a room, and announcing interest in a
+/// remotely-owned room.
+pub struct ClusterClient {
+    http: reqwest::Client,
+}
+
+impl ClusterClient {
+    pub fn new() -> Self {
+        ClusterClient { http: reqwest::Client::new() }
+    }
+
+    pub async fn forward_publish(&self, node: &str, publish: &ClusterPublish) -> Result<(), reqwest::Error> {
+        self.http
+            .post(format!("{}/cluster/publish", node))
+            .json(publish)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    pub async fn notify_subscribe(&self, node: &str, room: &str, subscriber_node: &str) -> Result<(), reqwest::Error> {
+        self.http
+            .post(format!("{}/cluster/subscribe", node))
+            .json(&ClusterSubscribe { room: room.to_string(), node: subscriber_node.to_string() })
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    pub async fn notify_unsubscribe(&self, node: &str, room: &str, subscriber_node: &str) -> Result<(), reqwest::Error> {
+        self.http
+            .post(format!("{}/cluster/unsubscribe", node))
+            .json(&ClusterUnsubscribe { room: room.to_string(), node: subscriber_node.to_string() })
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn owner_of_is_deterministic_and_agrees_across_instances() {
+        let nodes = vec!["http://a".to_string(), "http://b".to_string(), "http://c".to_string()];
+        let first = ClusterMetadata::new(nodes.clone());
+        let second = ClusterMetadata::new(nodes);
+        assert_eq!(first.owner_of("general"), second.owner_of("general"));
+        assert_eq!(first.owner_of("general"), first.owner_of("general"));
+    }
+
+    #[test]
+    fn owner_of_is_unaffected_by_input_node_order() {
+        let a = ClusterMetadata::new(vec!["http://a".to_string(), "http://b".to_string(), "http://c".to_string()]);
+        let b = ClusterMetadata::new(vec!["http://c".to_string(), "http://a".to_string(), "http://b".to_string()]);
+        for room in ["general", "random", "dev"] {
+            assert_eq!(a.owner_of(room), b.owner_of(room));
+        }
+    }
+
+    #[test]
+    fn single_node_cluster_is_always_local() {
+        let cluster = ClusterMetadata::new(vec!["http://solo".to_string()]);
+        assert!(cluster.is_local("anything", "http://solo"));
+        assert_eq!(cluster.owner_of("anything"), "http://solo");
+    }
+
+    #[test]
+    fn is_local_matches_owner_of() {
+        let cluster = ClusterMetadata::new(vec!["http://a".to_string(), "http://b".to_string()]);
+        let owner = cluster.owner_of("general").to_string();
+        assert!(cluster.is_local("general", &owner));
+        assert!(!cluster.is_local("general", "http://someone-else"));
+    }
+
+    #[test]
+    fn broadcasting_subscribe_and_unsubscribe() {
+        let broadcasting = Broadcasting::new();
+        assert!(broadcasting.subscribers_for("general").is_empty());
+
+        broadcasting.subscribe("general", "http://a");
+        broadcasting.subscribe("general", "http://b");
+        assert_eq!(broadcasting.subscribers_for("general").len(), 2);
+
+        broadcasting.unsubscribe("general", "http://a");
+        let remaining = broadcasting.subscribers_for("general");
+        assert_eq!(remaining.len(), 1);
+        assert!(remaining.contains("http://b"));
+    }
+
+    #[test]
+    fn broadcasting_unsubscribe_unknown_node_is_a_no_op() {
+        let broadcasting = Broadcasting::new();
+        broadcasting.subscribe("general", "http://a");
+        broadcasting.unsubscribe("general", "http://unknown");
+        assert_eq!(broadcasting.subscribers_for("general").len(), 1);
+    }
+}
@@ -1,14 +1,26 @@
 // src/main.rs
-use std::{collections::HashMap, sync::{Arc, Mutex}};
+use std::{collections::{HashMap, HashSet}, sync::{Arc, Mutex}};
 use tokio::net::TcpListener;
 use tokio_tungstenite::tungstenite::protocol::Message;
 use futures_util::{SinkExt, StreamExt};
 use tokio_tungstenite::accept_async;
 use clap::{Parser, Subcommand};
-use colored::*;
 use chrono::Local;
 use rusqlite::{params, Connection};
 
+mod auth;
+mod bot;
+mod client;
+mod cluster;
+mod metrics;
+mod protocol;
+mod rooms;
+
+use bot::{BotCommand, WaifuClient};
+use cluster::{Broadcasting, ClusterClient, ClusterMetadata, ClusterPublish, ClusterSubscribe, ClusterUnsubscribe};
+use metrics::{InfluxConfig, Metrics};
+use protocol::{RequestContainer, RequestId, RequestKind, ResponseContainer, ResponseKind};
+
 #[derive(Parser)]
 #[command(name = "ChatApp")]
 #[command(about = "Simple CLI Chat Application", long_about = None)]
@@ -19,13 +31,32 @@ struct Cli {
 
 #[derive(Subcommand)]
 enum Commands {
-    Server,
+    Server {
+        /// Address other nodes use to reach this node's cluster HTTP endpoint.
+        #[arg(long, default_value = "http://127.0.0.1:9090")]
+        node: String,
+        /// Comma-separated addresses of the other nodes in the cluster.
+        #[arg(long, value_delimiter = ',', default_value = "")]
+        peers: Vec<String>,
+        /// Port the Prometheus `/metrics` endpoint is bound on.
+        #[arg(long, default_value_t = 9100)]
+        metrics_port: u16,
+        /// Optional InfluxDB base URL to additionally push counters to.
+        #[arg(long)]
+        influx_url: Option<String>,
+        /// InfluxDB database name, used only when `--influx-url` is set.
+        #[arg(long, default_value = "chat_app")]
+        influx_db: String,
+        /// Base URL of the image API backing the `!waifu` bot command.
+        #[arg(long, default_value = "https://api.waifu.pics/sfw")]
+        waifu_api: String,
+    },
     Client,
 }
 
 #[derive(Debug, Clone)]
 enum ChatMessage {
-    Public { from: String, content: String, timestamp: String },
+    Public { from: String, room: String, content: String, timestamp: String },
     Private { from: String, to: String, content: String, timestamp: String },
     System(String),
 }
@@ -33,8 +64,8 @@ enum ChatMessage {
 impl ChatMessage {
     fn to_string(&self) -> String {
         match self {
-            ChatMessage::Public { from, content, timestamp } => {
-                format!("[{}][{}]: {}", timestamp, from, content)
+            ChatMessage::Public { from, room, content, timestamp } => {
+                format!("[{}][{}][{}]: {}", timestamp, room, from, content)
             }
             ChatMessage::Private { from, to, content, timestamp } => {
                 format!("[{}][Private from {} to {}]: {}", timestamp, from, to, content)
@@ -45,10 +76,10 @@ impl ChatMessage {
 
     fn log_to_db(&self, conn: &Connection) {
         match self {
-            ChatMessage::Public { from, content, timestamp } => {
+            ChatMessage::Public { from, room, content, timestamp } => {
                 let _ = conn.execute(
-                    "INSERT INTO messages (from_user, to_user, content, timestamp, is_private) VALUES (?1, NULL, ?2, ?3, 0)",
-                    params![from, content, timestamp],
+                    "INSERT INTO messages (from_user, to_user, content, timestamp, is_private, room) VALUES (?1, NULL, ?2, ?3, 0, ?4)",
+                    params![from, content, timestamp, room],
                 );
             }
             ChatMessage::Private { from, to, content, timestamp } => {
@@ -62,17 +93,107 @@ impl ChatMessage {
     }
 }
 
+fn push_to(senders: &HashMap<String, tokio::sync::mpsc::UnboundedSender<Message>>, user: &str, kind: ResponseKind) {
+    if let Some(tx) = senders.get(user) {
+        let _ = send_response(tx, ResponseContainer::push(kind));
+    }
+}
+
+fn send_response(tx: &tokio::sync::mpsc::UnboundedSender<Message>, response: ResponseContainer) -> Result<(), ()> {
+    let payload = serde_json::to_string(&response).map_err(|_| ())?;
+    tx.send(Message::Text(payload)).map_err(|_| ())
+}
+
+fn reply(tx: &tokio::sync::mpsc::UnboundedSender<Message>, request_id: RequestId, kind: ResponseKind) {
+    let _ = send_response(tx, ResponseContainer::reply(request_id, kind));
+}
+
 #[tokio::main]
 async fn main() -> std::io::Result<()> {
     let cli = Cli::parse();
 
     match cli.command {
-        Commands::Server => run_server().await,
+        Commands::Server { node, peers, metrics_port, influx_url, influx_db, waifu_api } => {
+            run_server(node, peers, metrics_port, influx_url, influx_db, waifu_api).await
+        }
         Commands::Client => run_client().await,
     }
 }
 
-async fn run_server() -> std::io::Result<()> {
+#[derive(Clone)]
+struct ClusterState {
+    conn: Arc<Mutex<Connection>>,
+    user_senders: Arc<Mutex<HashMap<String, tokio::sync::mpsc::UnboundedSender<Message>>>>,
+    room_members: Arc<Mutex<HashMap<String, HashSet<String>>>>,
+    broadcasting: Arc<Broadcasting>,
+    cluster_client: Arc<ClusterClient>,
+    metrics: Arc<Metrics>,
+}
+
+/// Delivers a publish forwarded from another node to this node's local
+/// subscribers, then re-forwards it to any other node in turn subscribed to
+/// the room through this (owning) node.
+async fn handle_cluster_publish(
+    axum::extract::State(state): axum::extract::State<ClusterState>,
+    axum::Json(publish): axum::Json<ClusterPublish>,
+) -> axum::http::StatusCode {
+    let chat = if publish.is_system {
+        ChatMessage::System(format!("[{}][{}][{}]: {}", publish.timestamp, publish.room, publish.from, publish.content))
+    } else {
+        ChatMessage::Public {
+            from: publish.from.clone(),
+            room: publish.room.clone(),
+            content: publish.content.clone(),
+            timestamp: publish.timestamp.clone(),
+        }
+    };
+    chat.log_to_db(&state.conn.lock().unwrap());
+    state.metrics.record_public(&publish.room);
+
+    let members = state.room_members.lock().unwrap().get(&publish.room).cloned().unwrap_or_default();
+    {
+        let senders = state.user_senders.lock().unwrap();
+        for member in &members {
+            push_to(&senders, member, ResponseKind::Message { line: chat.to_string() });
+        }
+    }
+
+    let remotes = state.broadcasting.subscribers_for(&publish.room);
+    for remote in remotes {
+        let _ = state.cluster_client.forward_publish(&remote, &publish).await;
+    }
+
+    axum::http::StatusCode::OK
+}
+
+async fn handle_cluster_subscribe(
+    axum::extract::State(state): axum::extract::State<ClusterState>,
+    axum::Json(subscribe): axum::Json<ClusterSubscribe>,
+) -> axum::http::StatusCode {
+    state.broadcasting.subscribe(&subscribe.room, &subscribe.node);
+    axum::http::StatusCode::OK
+}
+
+async fn handle_cluster_unsubscribe(
+    axum::extract::State(state): axum::extract::State<ClusterState>,
+    axum::Json(unsubscribe): axum::Json<ClusterUnsubscribe>,
+) -> axum::http::StatusCode {
+    state.broadcasting.unsubscribe(&unsubscribe.room, &unsubscribe.node);
+    axum::http::StatusCode::OK
+}
+
+async fn handle_metrics(axum::extract::State(metrics): axum::extract::State<Arc<Metrics>>) -> String {
+    metrics.render_prometheus()
+}
+
+async fn run_server(
+    node: String,
+    peers: Vec<String>,
+    metrics_port: u16,
+    influx_url: Option<String>,
+    influx_db: String,
+    waifu_api: String,
+) -> std::io::Result<()> {
     use tokio::sync::mpsc;
 
     let conn = Connection::open("chat_history.db").expect("Failed to open DB");
@@ -83,167 +204,523 @@ async fn run_server() -> std::io::Result<()> {
             to_user TEXT,
             content TEXT NOT NULL,
             timestamp TEXT NOT NULL,
-            is_private INTEGER NOT NULL
+            is_private INTEGER NOT NULL,
+            room TEXT
         )",
         [],
     ).expect("Failed to create table");
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_messages_timestamp ON messages(timestamp)",
+        [],
+    ).expect("Failed to create timestamp index");
+    auth::create_users_table(&conn).expect("Failed to create users table");
+    rooms::create_room_tables(&conn).expect("Failed to create room tables");
 
     let conn = Arc::new(Mutex::new(conn));
     let listener = TcpListener::bind("127.0.0.1:8080").await?;
     let user_senders: Arc<Mutex<HashMap<String, tokio::sync::mpsc::UnboundedSender<Message>>>> = Arc::new(Mutex::new(HashMap::new()));
+    let room_members: Arc<Mutex<HashMap<String, HashSet<String>>>> = Arc::new(Mutex::new(HashMap::new()));
+    let user_rooms: Arc<Mutex<HashMap<String, String>>> = Arc::new(Mutex::new(HashMap::new()));
+    if let Ok(memberships) = rooms::all_memberships(&conn.lock().unwrap()) {
+        let mut room_members_guard = room_members.lock().unwrap();
+        let mut user_rooms_guard = user_rooms.lock().unwrap();
+        for (member, room) in memberships {
+            room_members_guard.entry(room.clone()).or_insert_with(HashSet::new).insert(member.clone());
+            user_rooms_guard.insert(member, room);
+        }
+    }
+
+    let mut cluster_nodes = peers.clone();
+    cluster_nodes.push(node.clone());
+    let cluster = Arc::new(ClusterMetadata::new(cluster_nodes));
+    let broadcasting = Arc::new(Broadcasting::new());
+    let cluster_client = Arc::new(ClusterClient::new());
+    let waifu_client = Arc::new(WaifuClient::new(waifu_api));
+
+    let metrics = Arc::new(Metrics::new());
+
+    let cluster_state = ClusterState {
+        conn: Arc::clone(&conn),
+        user_senders: Arc::clone(&user_senders),
+        room_members: Arc::clone(&room_members),
+        broadcasting: Arc::clone(&broadcasting),
+        cluster_client: Arc::clone(&cluster_client),
+        metrics: Arc::clone(&metrics),
+    };
+    if let Some(addr) = node.strip_prefix("http://") {
+        if let Ok(socket_addr) = addr.parse::<std::net::SocketAddr>() {
+            let app = axum::Router::new()
+                .route("/cluster/publish", axum::routing::post(handle_cluster_publish))
+                .route("/cluster/subscribe", axum::routing::post(handle_cluster_subscribe))
+                .route("/cluster/unsubscribe", axum::routing::post(handle_cluster_unsubscribe))
+                .with_state(cluster_state);
+            tokio::spawn(async move {
+                if let Ok(listener) = tokio::net::TcpListener::bind(socket_addr).await {
+                    let _ = axum::serve(listener, app).await;
+                }
+            });
+        }
+    }
+
+    let metrics_app = axum::Router::new()
+        .route("/metrics", axum::routing::get(handle_metrics))
+        .with_state(Arc::clone(&metrics));
+    let metrics_addr: std::net::SocketAddr = ([127, 0, 0, 1], metrics_port).into();
+    tokio::spawn(async move {
+        if let Ok(listener) = tokio::net::TcpListener::bind(metrics_addr).await {
+            let _ = axum::serve(listener, metrics_app).await;
+        }
+    });
+
+    if let Some(url) = influx_url {
+        let influx_config = InfluxConfig { url, database: influx_db };
+        let metrics_for_influx = Arc::clone(&metrics);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(10));
+            loop {
+                interval.tick().await;
+                metrics::push_to_influx(&metrics_for_influx, &influx_config).await;
+            }
+        });
+    }
 
     loop {
         let (stream, _) = listener.accept().await?;
         let ws_stream = accept_async(stream).await.unwrap();
         let user_senders_clone = Arc::clone(&user_senders);
+        let room_members_clone = Arc::clone(&room_members);
+        let user_rooms_clone = Arc::clone(&user_rooms);
         let db_conn = Arc::clone(&conn);
+        let cluster_clone = Arc::clone(&cluster);
+        let broadcasting_clone = Arc::clone(&broadcasting);
+        let cluster_client_clone = Arc::clone(&cluster_client);
+        let self_node = node.clone();
+        let metrics_clone = Arc::clone(&metrics);
+        metrics_clone.connection_opened();
+        let waifu_client_clone = Arc::clone(&waifu_client);
 
         tokio::spawn(async move {
             let (mut ws_sender, mut ws_receiver) = ws_stream.split();
 
-            let username = match ws_receiver.next().await {
-                Some(Ok(Message::Text(name))) => name,
-                _ => {
-                    eprintln!("Failed to receive username.");
+            let username = loop {
+                let frame = match ws_receiver.next().await {
+                    Some(Ok(Message::Text(frame))) => frame,
+                    _ => {
+                        eprintln!("Failed to receive auth request.");
+                        return;
+                    }
+                };
+
+                let request: RequestContainer = match serde_json::from_str(&frame) {
+                    Ok(request) => request,
+                    Err(_) => {
+                        eprintln!("Failed to parse auth request.");
+                        return;
+                    }
+                };
+
+                let result = match &request.kind {
+                    RequestKind::Register { username, password } => {
+                        auth::register_user(&db_conn.lock().unwrap(), username, password).map(|_| username.clone())
+                    }
+                    RequestKind::Auth { username, password } => {
+                        auth::authenticate_user(&db_conn.lock().unwrap(), username, password).map(|_| username.clone())
+                    }
+                    _ => Err(auth::AuthError::InvalidCredentials),
+                };
+
+                let (reply_kind, outcome) = match result {
+                    Ok(name) => (ResponseKind::Ok, Some(name)),
+                    Err(e) => (ResponseKind::Error { message: e.to_string() }, None),
+                };
+
+                let response = ResponseContainer::reply(request.request_id, reply_kind);
+                let payload = serde_json::to_string(&response).unwrap();
+                if ws_sender.send(Message::Text(payload)).await.is_err() {
                     return;
                 }
+
+                if let Some(name) = outcome {
+                    break name;
+                }
             };
 
             let (tx_user, mut rx_user) = mpsc::unbounded_channel();
             user_senders_clone.lock().unwrap().insert(username.clone(), tx_user);
 
+            rooms::join_room(&db_conn.lock().unwrap(), &username, rooms::DEFAULT_ROOM).ok();
+            room_members_clone
+                .lock()
+                .unwrap()
+                .entry(rooms::DEFAULT_ROOM.to_string())
+                .or_insert_with(HashSet::new)
+                .insert(username.clone());
+            user_rooms_clone
+                .lock()
+                .unwrap()
+                .insert(username.clone(), rooms::DEFAULT_ROOM.to_string());
+
             let private_sender_task = tokio::spawn(async move {
                 while let Some(msg) = rx_user.recv().await {
                     if let Err(e) = ws_sender.send(msg).await {
-                        eprintln!("Failed to send private msg: {}", e);
+                        eprintln!("Failed to send message: {}", e);
                         break;
                     }
                 }
             });
 
             while let Some(Ok(msg)) = ws_receiver.next().await {
-                if let Message::Text(text) = msg {
-                    let now = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
-                    if text.starts_with("/msg ") {
-                        let parts: Vec<&str> = text[5..].splitn(2, ' ').collect();
-                        if parts.len() == 2 {
-                            let target = parts[0].trim();
-                            let message = parts[1].trim();
-                            let chat = ChatMessage::Private {
-                                from: username.clone(),
-                                to: target.to_string(),
-                                content: message.to_string(),
-                                timestamp: now,
-                            };
-                            chat.log_to_db(&db_conn.lock().unwrap());
-                            if let Some(tx) = user_senders_clone.lock().unwrap().get(target) {
-                                let _ = tx.send(Message::Text(chat.to_string()));
-                            } else {
-                                let err = ChatMessage::System(format!("[Error] User '{}' not found.", target));
-                                let _ = user_senders_clone.lock().unwrap().get(&username).map(|tx| tx.send(Message::Text(err.to_string())));
-                            }
+                let Message::Text(text) = msg else { continue };
+
+                let request: RequestContainer = match serde_json::from_str(&text) {
+                    Ok(request) => request,
+                    Err(_) => continue,
+                };
+                let request_id = request.request_id;
+                let now = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+
+                match request.kind {
+                    RequestKind::Register { .. } | RequestKind::Auth { .. } => {
+                        let senders = user_senders_clone.lock().unwrap();
+                        if let Some(tx) = senders.get(&username) {
+                            reply(tx, request_id, ResponseKind::Error { message: "already authenticated".to_string() });
                         }
-                    } else {
-                        let chat = ChatMessage::Public {
+                    }
+                    RequestKind::SendPrivate { to, content } => {
+                        let chat = ChatMessage::Private {
                             from: username.clone(),
-                            content: text.clone(),
+                            to: to.clone(),
+                            content,
                             timestamp: now,
                         };
                         chat.log_to_db(&db_conn.lock().unwrap());
-                        println!("{}", chat.to_string());
+                        metrics_clone.record_private();
+
+                        let senders = user_senders_clone.lock().unwrap();
+                        if let Some(target_tx) = senders.get(&to) {
+                            let _ = send_response(target_tx, ResponseContainer::push(ResponseKind::Message { line: chat.to_string() }));
+                        }
+                        if let Some(tx) = senders.get(&username) {
+                            match senders.get(&to) {
+                                Some(_) => reply(tx, request_id, ResponseKind::Ok),
+                                None => reply(tx, request_id, ResponseKind::Error { message: format!("User '{}' not found.", to) }),
+                            }
+                        }
                     }
-                }
-            }
+                    RequestKind::JoinRoom { room } => {
+                        let previous = user_rooms_clone.lock().unwrap().remove(&username);
+                        if let Some(current) = previous {
+                            let now_empty = {
+                                let mut members_guard = room_members_clone.lock().unwrap();
+                                match members_guard.get_mut(&current) {
+                                    Some(members) => {
+                                        members.remove(&username);
+                                        members.is_empty()
+                                    }
+                                    None => true,
+                                }
+                            };
+                            rooms::leave_room(&db_conn.lock().unwrap(), &username, &current).ok();
+                            if now_empty && current != room && !cluster_clone.is_local(&current, &self_node) {
+                                let owner = cluster_clone.owner_of(&current).to_string();
+                                let _ = cluster_client_clone.notify_unsubscribe(&owner, &current, &self_node).await;
+                            }
+                        }
+                        rooms::join_room(&db_conn.lock().unwrap(), &username, &room).ok();
+                        room_members_clone
+                            .lock()
+                            .unwrap()
+                            .entry(room.clone())
+                            .or_insert_with(HashSet::new)
+                            .insert(username.clone());
+                        user_rooms_clone.lock().unwrap().insert(username.clone(), room.clone());
 
-            let _ = private_sender_task.await;
-            user_senders_clone.lock().unwrap().remove(&username);
-        });
-    }
-}
-async fn run_client() -> std::io::Result<()> {
-    use tokio_tungstenite::connect_async;
-    use tokio::io::{AsyncBufReadExt, BufReader};
-    use std::io::Write;
-    use rusqlite::Connection;
+                        if !cluster_clone.is_local(&room, &self_node) {
+                            let owner = cluster_clone.owner_of(&room).to_string();
+                            let _ = cluster_client_clone.notify_subscribe(&owner, &room, &self_node).await;
+                        }
+
+                        let senders = user_senders_clone.lock().unwrap();
+                        if let Some(tx) = senders.get(&username) {
+                            reply(tx, request_id, ResponseKind::Message { line: format!("Joined room '{}'.", room) });
+                        }
+                    }
+                    RequestKind::LeaveRoom { room } => {
+                        let now_empty = {
+                            let mut members_guard = room_members_clone.lock().unwrap();
+                            match members_guard.get_mut(&room) {
+                                Some(members) => {
+                                    members.remove(&username);
+                                    members.is_empty()
+                                }
+                                None => true,
+                            }
+                        };
+                        rooms::leave_room(&db_conn.lock().unwrap(), &username, &room).ok();
+                        if user_rooms_clone.lock().unwrap().get(&username) == Some(&room) {
+                            user_rooms_clone.lock().unwrap().remove(&username);
+                        }
 
-    print!("Enter your username: ");
-    std::io::stdout().flush().unwrap();
-    let mut username = String::new();
-    std::io::stdin().read_line(&mut username).unwrap();
-    let username = username.trim().to_string();
+                        if now_empty && !cluster_clone.is_local(&room, &self_node) {
+                            let owner = cluster_clone.owner_of(&room).to_string();
+                            let _ = cluster_client_clone.notify_unsubscribe(&owner, &room, &self_node).await;
+                        }
 
-    let (ws_stream, _) = connect_async("ws://127.0.0.1:8080").await.expect("Failed to connect");
-    let (mut write, mut read) = ws_stream.split();
+                        let senders = user_senders_clone.lock().unwrap();
+                        if let Some(tx) = senders.get(&username) {
+                            reply(tx, request_id, ResponseKind::Message { line: format!("Left room '{}'.", room) });
+                        }
+                    }
+                    RequestKind::ListRooms => {
+                        let names = rooms::list_rooms(&db_conn.lock().unwrap())
+                            .map(|rows| rows.into_iter().map(|(name, _)| name).collect::<Vec<_>>())
+                            .unwrap_or_default();
 
-    write.send(Message::Text(username.clone())).await.unwrap();
+                        let senders = user_senders_clone.lock().unwrap();
+                        if let Some(tx) = senders.get(&username) {
+                            reply(tx, request_id, ResponseKind::RoomList { rooms: names });
+                        }
+                    }
+                    RequestKind::SetTopic { room, topic } => {
+                        rooms::set_topic(&db_conn.lock().unwrap(), &room, &topic).ok();
+                        let line = format!("Topic for '{}' set to: {}", room, topic);
+                        let members = room_members_clone.lock().unwrap().get(&room).cloned().unwrap_or_default();
 
-    let stdin = BufReader::new(tokio::io::stdin());
-    let mut lines = stdin.lines();
+                        let senders = user_senders_clone.lock().unwrap();
+                        for member in &members {
+                            push_to(&senders, member, ResponseKind::Message { line: line.clone() });
+                        }
+                        if let Some(tx) = senders.get(&username) {
+                            reply(tx, request_id, ResponseKind::Ok);
+                        }
+                    }
+                    RequestKind::SendPublic { content } => {
+                        let (effective_from, effective_content, is_bot) = match bot::parse(&content) {
+                            Some(BotCommand::Owoify(text)) => ("Bot".to_string(), bot::owoify(&text), true),
+                            Some(BotCommand::Mock(text)) => ("Bot".to_string(), bot::mock(&text), true),
+                            Some(BotCommand::Leet(text)) => ("Bot".to_string(), bot::leet(&text), true),
+                            Some(BotCommand::Calc(expr)) => {
+                                let reply = match bot::calc(&expr) {
+                                    Ok(value) => format!("{} = {}", expr.trim(), value),
+                                    Err(e) => format!("Error evaluating '{}': {}", expr.trim(), e),
+                                };
+                                ("Bot".to_string(), reply, true)
+                            }
+                            Some(BotCommand::Waifu(category)) => {
+                                let reply = match waifu_client_clone.fetch_image_url(&category).await {
+                                    Ok(url) => url,
+                                    Err(e) => format!("Failed to fetch image: {}", e),
+                                };
+                                ("Bot".to_string(), reply, true)
+                            }
+                            None => (username.clone(), content, false),
+                        };
 
-    tokio::spawn(async move {
-        while let Some(Ok(msg)) = read.next().await {
-            if let Message::Text(txt) = msg {
-                if txt.contains(&username) {
-                    println!("{}", txt.green());
-                } else {
-                    println!("{}", txt.cyan());
-                }
-            }
-        }
-    });
+                        let current_room = user_rooms_clone.lock().unwrap().get(&username).cloned();
+                        match current_room {
+                            Some(room) => {
+                                if !cluster_clone.is_local(&room, &self_node) {
+                                    let owner = cluster_clone.owner_of(&room).to_string();
+                                    let publish = ClusterPublish {
+                                        room: room.clone(),
+                                        from: effective_from,
+                                        content: effective_content,
+                                        timestamp: now,
+                                        is_system: is_bot,
+                                    };
+                                    let outcome = cluster_client_clone.forward_publish(&owner, &publish).await;
+
+                                    let senders = user_senders_clone.lock().unwrap();
+                                    if let Some(tx) = senders.get(&username) {
+                                        match outcome {
+                                            Ok(()) => reply(tx, request_id, ResponseKind::Ok),
+                                            Err(e) => reply(tx, request_id, ResponseKind::Error { message: format!("Failed to reach node '{}': {}", owner, e) }),
+                                        }
+                                    }
+                                    continue;
+                                }
 
-    while let Ok(Some(line)) = lines.next_line().await {
-        if line.starts_with("/history") {
-            let conn = Connection::open("chat_history.db").expect("Failed to open DB");
-            println!("--- Message History ---");
-            let mut stmt = conn.prepare("SELECT timestamp, from_user, to_user, content, is_private FROM messages ORDER BY id DESC LIMIT 10").unwrap();
-            let rows = stmt.query_map([], |row| {
-                Ok((
-                    row.get::<_, String>(0)?,
-                    row.get::<_, String>(1)?,
-                    row.get::<_, Option<String>>(2)?,
-                    row.get::<_, String>(3)?,
-                    row.get::<_, i32>(4)?
-                ))
-            }).unwrap();
-            for row in rows {
-                if let Ok((ts, from, to, content, private)) = row {
-                    if private == 1 {
-                        println!("[{}][Private from {} to {}]: {}", ts, from, to.unwrap_or("?".to_string()), content);
-                    } else {
-                        println!("[{}][{}]: {}", ts, from, content);
+                                let chat = if is_bot {
+                                    ChatMessage::System(format!("[{}][{}][{}]: {}", now, room, effective_from, effective_content))
+                                } else {
+                                    ChatMessage::Public {
+                                        from: effective_from.clone(),
+                                        room: room.clone(),
+                                        content: effective_content.clone(),
+                                        timestamp: now.clone(),
+                                    }
+                                };
+                                chat.log_to_db(&db_conn.lock().unwrap());
+                                metrics_clone.record_public(&room);
+                                println!("{}", chat.to_string());
+
+                                let members = room_members_clone.lock().unwrap().get(&room).cloned().unwrap_or_default();
+                                {
+                                    let senders = user_senders_clone.lock().unwrap();
+                                    for member in &members {
+                                        push_to(&senders, member, ResponseKind::Message { line: chat.to_string() });
+                                    }
+                                    if let Some(tx) = senders.get(&username) {
+                                        reply(tx, request_id, ResponseKind::Ok);
+                                    }
+                                }
+
+                                let remotes = broadcasting_clone.subscribers_for(&room);
+                                if !remotes.is_empty() {
+                                    let publish = ClusterPublish { room: room.clone(), from: effective_from, content: effective_content, timestamp: now, is_system: is_bot };
+                                    for remote in remotes {
+                                        let _ = cluster_client_clone.forward_publish(&remote, &publish).await;
+                                    }
+                                }
+                            }
+                            None => {
+                                let senders = user_senders_clone.lock().unwrap();
+                                if let Some(tx) = senders.get(&username) {
+                                    reply(tx, request_id, ResponseKind::Error { message: "Join a room with /join <room> before sending messages.".to_string() });
+                                }
+                            }
+                        }
+                    }
+                    RequestKind::QueryHistory { limit, before, after, room, peer } => {
+                        let rows = query_history(
+                            &db_conn.lock().unwrap(),
+                            limit,
+                            before,
+                            after,
+                            room.as_deref(),
+                            peer.as_deref(),
+                        );
+                        let senders = user_senders_clone.lock().unwrap();
+                        if let Some(tx) = senders.get(&username) {
+                            reply(tx, request_id, ResponseKind::HistoryBegin);
+                            for (id, line) in rows {
+                                reply(tx, request_id, ResponseKind::HistoryRow { id, line });
+                            }
+                            reply(tx, request_id, ResponseKind::HistoryEnd);
+                        }
+                    }
+                    RequestKind::Search { keyword } => {
+                        let rows = search_history(&db_conn.lock().unwrap(), &keyword);
+                        let senders = user_senders_clone.lock().unwrap();
+                        if let Some(tx) = senders.get(&username) {
+                            reply(tx, request_id, ResponseKind::HistoryBegin);
+                            for (id, line) in rows {
+                                reply(tx, request_id, ResponseKind::HistoryRow { id, line });
+                            }
+                            reply(tx, request_id, ResponseKind::HistoryEnd);
+                        }
                     }
                 }
             }
-        } else if line.starts_with("/search ") {
-            let keyword = line[8..].trim();
-            let conn = Connection::open("chat_history.db").expect("Failed to open DB");
-            println!("--- Search Results for '{}': ---", keyword);
-            let query = format!("%{}%", keyword);
-            let mut stmt = conn.prepare("SELECT timestamp, from_user, to_user, content, is_private FROM messages WHERE content LIKE ? ORDER BY id DESC LIMIT 10").unwrap();
-            let rows = stmt.query_map([query], |row| {
-                Ok((
-                    row.get::<_, String>(0)?,
-                    row.get::<_, String>(1)?,
-                    row.get::<_, Option<String>>(2)?,
-                    row.get::<_, String>(3)?,
-                    row.get::<_, i32>(4)?
-                ))
-            }).unwrap();
-            for row in rows {
-                if let Ok((ts, from, to, content, private)) = row {
-                    if private == 1 {
-                        println!("[{}][Private from {} to {}]: {}", ts, from, to.unwrap_or("?".to_string()), content);
-                    } else {
-                        println!("[{}][{}]: {}", ts, from, content);
+
+            let _ = private_sender_task.await;
+            metrics_clone.connection_closed();
+            user_senders_clone.lock().unwrap().remove(&username);
+            let current = user_rooms_clone.lock().unwrap().remove(&username);
+            if let Some(current) = current {
+                let now_empty = {
+                    let mut members_guard = room_members_clone.lock().unwrap();
+                    match members_guard.get_mut(&current) {
+                        Some(members) => {
+                            members.remove(&username);
+                            members.is_empty()
+                        }
+                        None => true,
                     }
+                };
+                if now_empty && !cluster_clone.is_local(&current, &self_node) {
+                    let owner = cluster_clone.owner_of(&current).to_string();
+                    let _ = cluster_client_clone.notify_unsubscribe(&owner, &current, &self_node).await;
                 }
             }
-        } else {
-            write.send(Message::Text(line)).await.unwrap();
-        }
+        });
     }
+}
+
+fn format_history_row(ts: &str, from: &str, to: &Option<String>, content: &str, is_private: i32) -> String {
+    if is_private == 1 {
+        format!("[{}][Private from {} to {}]: {}", ts, from, to.as_deref().unwrap_or("?"), content)
+    } else {
+        format!("[{}][{}]: {}", ts, from, content)
+    }
+}
+
+fn query_history(
+    conn: &Connection,
+    limit: u32,
+    before: Option<i64>,
+    after: Option<i64>,
+    room: Option<&str>,
+    peer: Option<&str>,
+) -> Vec<(i64, String)> {
+    let mut sql = String::from("SELECT id, timestamp, from_user, to_user, content, is_private FROM messages WHERE 1 = 1");
+    let mut query_params: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+
+    // Pages on `id`, the table's true total order, rather than `timestamp`:
+    // several messages can share a timestamp, and a page boundary drawn on
+    // timestamp alone would drop or duplicate rows within that same second.
+    if let Some(before) = before {
+        sql.push_str(" AND id < ?");
+        query_params.push(Box::new(before));
+    }
+    if let Some(after) = after {
+        sql.push_str(" AND id > ?");
+        query_params.push(Box::new(after));
+    }
+    if let Some(room) = room {
+        sql.push_str(" AND room = ?");
+        query_params.push(Box::new(room.to_string()));
+    }
+    if let Some(peer) = peer {
+        sql.push_str(" AND (from_user = ? OR to_user = ?)");
+        query_params.push(Box::new(peer.to_string()));
+        query_params.push(Box::new(peer.to_string()));
+    }
+    sql.push_str(" ORDER BY id DESC LIMIT ?");
+    query_params.push(Box::new(limit));
+
+    let mut stmt = conn.prepare(&sql).unwrap();
+    let param_refs: Vec<&dyn rusqlite::ToSql> = query_params.iter().map(|p| p.as_ref()).collect();
+    let rows = stmt
+        .query_map(param_refs.as_slice(), |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, Option<String>>(3)?,
+                row.get::<_, String>(4)?,
+                row.get::<_, i32>(5)?,
+            ))
+        })
+        .unwrap();
+
+    rows.flatten()
+        .map(|(id, ts, from, to, content, private)| (id, format_history_row(&ts, &from, &to, &content, private)))
+        .collect()
+}
+
+fn search_history(conn: &Connection, keyword: &str) -> Vec<(i64, String)> {
+    let mut stmt = conn
+        .prepare("SELECT id, timestamp, from_user, to_user, content, is_private FROM messages WHERE content LIKE ?1 ORDER BY id DESC LIMIT 10")
+        .unwrap();
+    let rows = stmt
+        .query_map(params![format!("%{}%", keyword)], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, Option<String>>(3)?,
+                row.get::<_, String>(4)?,
+                row.get::<_, i32>(5)?,
+            ))
+        })
+        .unwrap();
 
-    Ok(())
+    rows.flatten()
+        .map(|(id, ts, from, to, content, private)| (id, format_history_row(&ts, &from, &to, &content, private)))
+        .collect()
 }
 
+async fn run_client() -> std::io::Result<()> {
+    client::run().await
+}
@@ -0,0 +1,144 @@
+// src/metrics.rs
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// Counters tracked for the `/metrics` endpoint. The totals and the connection
+/// gauge are atomics, so reading them never blocks a sender; the per-room
+/// counts sit behind a `Mutex` that both `record_public` and
+/// `render_prometheus` take.
+#[derive(Default)]
+pub struct Metrics {
+    messages_sent: AtomicU64,
+    public_messages: AtomicU64,
+    private_messages: AtomicU64,
+    active_connections: AtomicU64,
+    room_messages: Mutex<HashMap<String, u64>>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Metrics::default()
+    }
+
+    pub fn record_public(&self, room: &str) {
+        self.messages_sent.fetch_add(1, Ordering::Relaxed);
+        self.public_messages.fetch_add(1, Ordering::Relaxed);
+        *self.room_messages.lock().unwrap().entry(room.to_string()).or_insert(0) += 1;
+    }
+
+    pub fn record_private(&self) {
+        self.messages_sent.fetch_add(1, Ordering::Relaxed);
+        self.private_messages.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn connection_opened(&self) {
+        self.active_connections.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn connection_closed(&self) {
+        self.active_connections.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub fn render_prometheus(&self) -> String {
+        let mut out = String::new();
+        out.push_str("# HELP chat_messages_sent_total Total number of chat messages sent.\n");
+        out.push_str("# TYPE chat_messages_sent_total counter\n");
+        out.push_str(&format!("chat_messages_sent_total {}\n", self.messages_sent.load(Ordering::Relaxed)));
+
+        out.push_str("# HELP chat_public_messages_total Total number of public room messages.\n");
+        out.push_str("# TYPE chat_public_messages_total counter\n");
+        out.push_str(&format!("chat_public_messages_total {}\n", self.public_messages.load(Ordering::Relaxed)));
+
+        out.push_str("# HELP chat_private_messages_total Total number of private messages.\n");
+        out.push_str("# TYPE chat_private_messages_total counter\n");
+        out.push_str(&format!("chat_private_messages_total {}\n", self.private_messages.load(Ordering::Relaxed)));
+
+        out.push_str("# HELP chat_active_connections Current number of connected clients.\n");
+        out.push_str("# TYPE chat_active_connections gauge\n");
+        out.push_str(&format!("chat_active_connections {}\n", self.active_connections.load(Ordering::Relaxed)));
+
+        out.push_str("# HELP chat_room_messages_total Total number of public messages sent per room.\n");
+        out.push_str("# TYPE chat_room_messages_total counter\n");
+        for (room, count) in self.room_messages.lock().unwrap().iter() {
+            out.push_str(&format!("chat_room_messages_total{{room=\"{}\"}} {}\n", room, count));
+        }
+
+        out
+    }
+
+    fn snapshot_line(&self) -> String {
+        format!(
+            "chat_metrics messages_sent={},public_messages={},private_messages={},active_connections={}",
+            self.messages_sent.load(Ordering::Relaxed),
+            self.public_messages.load(Ordering::Relaxed),
+            self.private_messages.load(Ordering::Relaxed),
+            self.active_connections.load(Ordering::Relaxed),
+        )
+    }
+}
+
+/// Configuration for pushing the total counters and the connection gauge to an
+/// InfluxDB line-protocol endpoint; the per-room counts are not pushed.
+pub struct InfluxConfig {
+    pub url: String,
+    pub database: String,
+}
+
+pub async fn push_to_influx(metrics: &Metrics, config: &InfluxConfig) {
+    let client = reqwest::Client::new();
+    let line = metrics.snapshot_line();
+    let _ = client
+        .post(format!("{}/write?db={}", config.url, config.database))
+        .body(line)
+        .send()
+        .await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_public_increments_total_and_public_and_per_room_counters() {
+        let metrics = Metrics::new();
+        metrics.record_public("general");
+        metrics.record_public("general");
+        metrics.record_public("dev");
+        let rendered = metrics.render_prometheus();
+        assert!(rendered.contains("chat_messages_sent_total 3"));
+        assert!(rendered.contains("chat_public_messages_total 3"));
+        assert!(rendered.contains("chat_room_messages_total{room=\"general\"} 2"));
+        assert!(rendered.contains("chat_room_messages_total{room=\"dev\"} 1"));
+    }
+
+    #[test]
+    fn record_private_increments_total_and_private_only() {
+        let metrics = Metrics::new();
+        metrics.record_private();
+        let rendered = metrics.render_prometheus();
+        assert!(rendered.contains("chat_messages_sent_total 1"));
+        assert!(rendered.contains("chat_private_messages_total 1"));
+        assert!(rendered.contains("chat_public_messages_total 0"));
+    }
+
+    #[test]
+    fn connection_opened_and_closed_adjust_the_gauge() {
+        let metrics = Metrics::new();
+        metrics.connection_opened();
+        metrics.connection_opened();
+        metrics.connection_closed();
+        assert!(metrics.render_prometheus().contains("chat_active_connections 1"));
+    }
+
+    #[test]
+    fn snapshot_line_reflects_recorded_counters() {
+        let metrics = Metrics::new();
+        metrics.record_public("general");
+        metrics.record_private();
+        let line = metrics.snapshot_line();
+        assert!(line.contains("messages_sent=2"));
+        assert!(line.contains("public_messages=1"));
+        assert!(line.contains("private_messages=1"));
+    }
+}
@@ -0,0 +1,68 @@
+// src/protocol.rs
+use serde::{Deserialize, Serialize};
+
+/// Correlates a `ResponseContainer` with the `RequestContainer` that produced it.
+/// A `request_id` of `0` marks a server-pushed message not tied to any request
+/// (an incoming chat line, for example).
+pub type RequestId = u64;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RequestContainer {
+    pub request_id: RequestId,
+    pub kind: RequestKind,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum RequestKind {
+    Register { username: String, password: String },
+    Auth { username: String, password: String },
+    SendPublic { content: String },
+    SendPrivate { to: String, content: String },
+    JoinRoom { room: String },
+    LeaveRoom { room: String },
+    ListRooms,
+    SetTopic { room: String, topic: String },
+    /// `before`/`after` page on the message `id` (its true total order) rather
+    /// than its timestamp, so same-second bursts of messages never fall
+    /// between two pages.
+    QueryHistory {
+        limit: u32,
+        before: Option<i64>,
+        after: Option<i64>,
+        room: Option<String>,
+        peer: Option<String>,
+    },
+    Search { keyword: String },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ResponseContainer {
+    pub request_id: RequestId,
+    pub kind: ResponseKind,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum ResponseKind {
+    Ok,
+    Error { message: String },
+    Message { line: String },
+    /// Marks the start of a contiguous block of `HistoryRow`s, so the client
+    /// can render it distinctly from interleaved live messages.
+    HistoryBegin,
+    HistoryRow { id: i64, line: String },
+    HistoryEnd,
+    RoomList { rooms: Vec<String> },
+}
+
+impl ResponseContainer {
+    pub fn reply(request_id: RequestId, kind: ResponseKind) -> Self {
+        ResponseContainer { request_id, kind }
+    }
+
+    /// A push not tied to any particular request, e.g. an incoming chat line.
+    pub fn push(kind: ResponseKind) -> Self {
+        ResponseContainer { request_id: 0, kind }
+    }
+}
@@ -0,0 +1,128 @@
+// src/rooms.rs
+use rusqlite::{params, Connection};
+
+pub const DEFAULT_ROOM: &str = "general";
+
+pub fn create_room_tables(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS rooms (
+            name TEXT PRIMARY KEY,
+            topic TEXT
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS room_memberships (
+            username TEXT NOT NULL,
+            room TEXT NOT NULL,
+            PRIMARY KEY (username, room)
+        )",
+        [],
+    )?;
+    conn.execute(
+        "INSERT OR IGNORE INTO rooms (name, topic) VALUES (?1, NULL)",
+        params![DEFAULT_ROOM],
+    )?;
+    Ok(())
+}
+
+pub fn join_room(conn: &Connection, username: &str, room: &str) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT OR IGNORE INTO rooms (name, topic) VALUES (?1, NULL)",
+        params![room],
+    )?;
+    conn.execute(
+        "INSERT OR IGNORE INTO room_memberships (username, room) VALUES (?1, ?2)",
+        params![username, room],
+    )?;
+    Ok(())
+}
+
+pub fn leave_room(conn: &Connection, username: &str, room: &str) -> rusqlite::Result<()> {
+    conn.execute(
+        "DELETE FROM room_memberships WHERE username = ?1 AND room = ?2",
+        params![username, room],
+    )?;
+    Ok(())
+}
+
+pub fn list_rooms(conn: &Connection) -> rusqlite::Result<Vec<(String, Option<String>)>> {
+    let mut stmt = conn.prepare("SELECT name, topic FROM rooms ORDER BY name")?;
+    let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+    rows.collect()
+}
+
+pub fn set_topic(conn: &Connection, room: &str, topic: &str) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT OR IGNORE INTO rooms (name, topic) VALUES (?1, NULL)",
+        params![room],
+    )?;
+    conn.execute(
+        "UPDATE rooms SET topic = ?1 WHERE name = ?2",
+        params![topic, room],
+    )?;
+    Ok(())
+}
+
+/// All persisted `(username, room)` memberships, used to repopulate the
+/// in-memory room/membership maps on server startup.
+pub fn all_memberships(conn: &Connection) -> rusqlite::Result<Vec<(String, String)>> {
+    let mut stmt = conn.prepare("SELECT username, room FROM room_memberships")?;
+    let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+    rows.collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        create_room_tables(&conn).unwrap();
+        conn
+    }
+
+    #[test]
+    fn default_room_exists_after_setup() {
+        let conn = setup();
+        let rooms = list_rooms(&conn).unwrap();
+        assert!(rooms.iter().any(|(name, _)| name == DEFAULT_ROOM));
+    }
+
+    #[test]
+    fn join_room_creates_room_and_membership() {
+        let conn = setup();
+        join_room(&conn, "alice", "dev").unwrap();
+        assert!(list_rooms(&conn).unwrap().iter().any(|(name, _)| name == "dev"));
+        assert_eq!(all_memberships(&conn).unwrap(), vec![("alice".to_string(), "dev".to_string())]);
+    }
+
+    #[test]
+    fn join_room_is_idempotent() {
+        let conn = setup();
+        join_room(&conn, "alice", "dev").unwrap();
+        join_room(&conn, "alice", "dev").unwrap();
+        assert_eq!(all_memberships(&conn).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn leave_room_removes_membership_but_keeps_room() {
+        let conn = setup();
+        join_room(&conn, "alice", "dev").unwrap();
+        leave_room(&conn, "alice", "dev").unwrap();
+        assert!(all_memberships(&conn).unwrap().is_empty());
+        assert!(list_rooms(&conn).unwrap().iter().any(|(name, _)| name == "dev"));
+    }
+
+    #[test]
+    fn set_topic_creates_room_if_missing_and_updates_existing() {
+        let conn = setup();
+        set_topic(&conn, "dev", "rust stuff").unwrap();
+        let rooms = list_rooms(&conn).unwrap();
+        assert_eq!(rooms.iter().find(|(name, _)| name == "dev").unwrap().1, Some("rust stuff".to_string()));
+
+        set_topic(&conn, "dev", "more rust stuff").unwrap();
+        let rooms = list_rooms(&conn).unwrap();
+        assert_eq!(rooms.iter().find(|(name, _)| name == "dev").unwrap().1, Some("more rust stuff".to_string()));
+    }
+}